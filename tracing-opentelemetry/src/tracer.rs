@@ -40,10 +40,19 @@ pub trait PreSampledTracer {
 
 impl PreSampledTracer for otel::NoopTracer {
     fn sampled_span_reference(&self, builder: &mut otel::SpanBuilder) -> otel::SpanReference {
-        builder
-            .parent_reference
-            .clone()
-            .unwrap_or_else(otel::SpanReference::empty_context)
+        if let Some(parent_reference) = builder.parent_reference.as_ref() {
+            return parent_reference.clone();
+        }
+
+        // No explicit parent was set on the builder: fall back to the globally
+        // active span so that applications installing no SDK still propagate
+        // the active trace context over W3C headers.
+        let current_reference = otel::Context::current().span().span_reference();
+        if current_reference.is_valid() {
+            current_reference
+        } else {
+            otel::SpanReference::empty_context()
+        }
     }
 
     fn new_trace_id(&self) -> otel::TraceId {
@@ -62,56 +71,129 @@ impl PreSampledTracer for Tracer {
                 .map(|provider| provider.config().id_generator.new_span_id())
                 .unwrap_or_else(otel::SpanId::invalid)
         });
-        let (trace_id, trace_flags) = builder
-            .parent_reference
-            .as_ref()
-            .filter(|parent_reference| parent_reference.is_valid())
-            .map(|parent_reference| (parent_reference.trace_id(), parent_reference.trace_flags()))
+        // When the builder has no explicit parent reference and no SDK is
+        // installed, there is no sampler to consult: fall back to the globally
+        // active span so pass-through services that only propagate trace
+        // identifiers still round-trip W3C headers correctly.
+        let current_reference;
+        let parent_reference = match builder.parent_reference.as_ref() {
+            Some(parent_reference) if parent_reference.is_valid() => Some(parent_reference),
+            Some(_) => None,
+            None if self.provider().is_none() => {
+                current_reference = otel::Context::current().span().span_reference();
+                if current_reference.is_valid() {
+                    Some(&current_reference)
+                } else {
+                    None
+                }
+            }
+            None => None,
+        };
+        // Without a provider there is no id generator either: a reference built on
+        // the active span keeps that span's id, so it stays valid to inject.
+        let span_id = match parent_reference {
+            Some(parent_reference)
+                if builder.span_id.is_none() && builder.parent_reference.is_none() =>
+            {
+                parent_reference.span_id()
+            }
+            _ => span_id,
+        };
+
+        // Trace ids may not change once a span has a valid parent, but per spec the
+        // configured sampler must still run on every span: it is what makes
+        // `ParentBased` and ratio/rate-limiting samplers work correctly under a
+        // sampled parent.
+        let trace_id = parent_reference
+            .map(|parent_reference| parent_reference.trace_id())
             .unwrap_or_else(|| {
-                let trace_id = builder.trace_id.unwrap_or_else(|| {
+                builder.trace_id.unwrap_or_else(|| {
                     self.provider()
                         .map(|provider| provider.config().id_generator.new_trace_id())
                         .unwrap_or_else(otel::TraceId::invalid)
-                });
-
-                // ensure sampling decision is recorded so all span references have consistent flags
-                let sampling_decision = if let Some(result) = builder.sampling_result.as_ref() {
-                    result.decision.clone()
-                } else if let Some(provider) = self.provider().as_ref() {
-                    let mut result = provider.config().default_sampler.should_sample(
-                        builder.parent_reference.as_ref(),
-                        trace_id,
-                        &builder.name,
-                        builder
-                            .span_kind
-                            .as_ref()
-                            .unwrap_or(&otel::SpanKind::Internal),
-                        builder.attributes.as_ref().unwrap_or(&Vec::new()),
-                        builder.links.as_ref().unwrap_or(&Vec::new()),
-                    );
-
-                    // Record additional attributes resulting from sampling
-                    if let Some(attributes) = &mut builder.attributes {
-                        attributes.append(&mut result.attributes)
-                    } else {
-                        builder.attributes = Some(result.attributes);
-                    }
-
-                    result.decision
-                } else {
-                    SamplingDecision::Drop
-                };
+                })
+            });
 
-                let trace_flags = if sampling_decision == SamplingDecision::RecordAndSample {
-                    otel::TRACE_FLAG_SAMPLED
-                } else {
-                    0
-                };
+        // Inherit the parent's trace state by default; a sampler that mutates the
+        // trace state to record its decision (e.g. to append a vendor entry) takes
+        // precedence below.
+        let parent_trace_state = parent_reference
+            .map(|parent_reference| parent_reference.trace_state().clone())
+            .unwrap_or_default();
 
-                (trace_id, trace_flags)
-            });
+        let (trace_flags, trace_state) = if let Some(result) = builder.sampling_result.as_ref() {
+            // Already sampled, e.g. by a previous call to this method: reuse the
+            // cached decision so all span references have consistent flags.
+            let trace_flags = if result.decision == SamplingDecision::RecordAndSample {
+                otel::TRACE_FLAG_SAMPLED
+            } else {
+                0
+            };
+            // Most samplers don't touch `trace_state` at all and leave it at its
+            // default, empty value — in that case the parent's tracestate must
+            // still win, or vendor entries get silently dropped the moment any
+            // provider is installed.
+            let trace_state = if result.trace_state.is_empty() {
+                parent_trace_state.clone()
+            } else {
+                result.trace_state.clone()
+            };
+            (trace_flags, trace_state)
+        } else if let Some(provider) = self.provider().as_ref() {
+            let mut result = provider.config().default_sampler.should_sample(
+                parent_reference,
+                trace_id,
+                &builder.name,
+                builder
+                    .span_kind
+                    .as_ref()
+                    .unwrap_or(&otel::SpanKind::Internal),
+                builder.attributes.as_ref().unwrap_or(&Vec::new()),
+                builder.links.as_ref().unwrap_or(&Vec::new()),
+            );
+
+            // Record additional attributes resulting from sampling
+            if let Some(attributes) = &mut builder.attributes {
+                attributes.append(&mut result.attributes)
+            } else {
+                builder.attributes = Some(result.attributes);
+            }
+
+            let trace_flags = if result.decision == SamplingDecision::RecordAndSample {
+                otel::TRACE_FLAG_SAMPLED
+            } else {
+                0
+            };
+            // Same as above: only let the sampler's trace state override the
+            // parent's if it actually set one.
+            let trace_state = if result.trace_state.is_empty() {
+                parent_trace_state.clone()
+            } else {
+                result.trace_state.clone()
+            };
+
+            // Cache the decision so the real span export later reuses it instead of
+            // recomputing it.
+            builder.sampling_result = Some(result);
+
+            (trace_flags, trace_state)
+        } else {
+            // No provider/sampler available: fall back to inheriting the parent's
+            // raw flags and trace state.
+            let trace_flags = parent_reference
+                .map(|parent_reference| parent_reference.trace_flags())
+                .unwrap_or(0);
+            (trace_flags, parent_trace_state)
+        };
 
-        otel::SpanReference::new(trace_id, span_id, trace_flags, false, Default::default())
+        // Deliberately not threading remoteness into this output reference: this
+        // method always builds a locally-created span's own reference, never one
+        // extracted from a remote context, so per spec `is_remote` stays `false`
+        // regardless of the parent's remoteness. The half of chunk0-4 that
+        // mattered — the sampler seeing the parent's true remoteness — is already
+        // satisfied above, since `should_sample` is called with the untouched
+        // `parent_reference` rather than this reconstructed one.
+        otel::SpanReference::new(trace_id, span_id, trace_flags, false, trace_state)
     }
 
     fn new_trace_id(&self) -> otel::TraceId {
@@ -130,8 +212,11 @@ impl PreSampledTracer for Tracer {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use opentelemetry::api::trace::{SpanBuilder, TracerProvider};
+    use opentelemetry::api::trace::{SpanBuilder, SpanId, SpanReference, TraceId, TracerProvider};
     use opentelemetry::sdk;
+    use opentelemetry::sdk::trace::{Config, SamplingResult, ShouldSample};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
 
     #[test]
     fn assigns_default_ids_if_missing() {
@@ -144,4 +229,229 @@ mod tests {
 
         assert!(span_reference.is_valid());
     }
+
+    #[derive(Debug)]
+    struct CountingSampler {
+        calls: Arc<AtomicUsize>,
+        decision: SamplingDecision,
+        trace_state: otel::TraceState,
+    }
+
+    impl ShouldSample for CountingSampler {
+        fn should_sample(
+            &self,
+            _parent_context: Option<&otel::SpanReference>,
+            _trace_id: TraceId,
+            _name: &str,
+            _span_kind: &otel::SpanKind,
+            _attributes: &Vec<otel::KeyValue>,
+            _links: &Vec<otel::Link>,
+        ) -> SamplingResult {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            SamplingResult {
+                decision: self.decision.clone(),
+                attributes: Vec::new(),
+                trace_state: self.trace_state.clone(),
+            }
+        }
+    }
+
+    fn tracer_with_sampler(decision: SamplingDecision) -> (Tracer, Arc<AtomicUsize>) {
+        tracer_with_sampler_and_trace_state(decision, Default::default())
+    }
+
+    fn tracer_with_sampler_and_trace_state(
+        decision: SamplingDecision,
+        trace_state: otel::TraceState,
+    ) -> (Tracer, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let sampler = CountingSampler {
+            calls: calls.clone(),
+            decision,
+            trace_state,
+        };
+        let provider = sdk::trace::TracerProvider::builder()
+            .with_config(Config::default().with_sampler(sampler))
+            .build();
+        (provider.get_tracer("test", None), calls)
+    }
+
+    #[test]
+    fn sampler_runs_even_with_a_valid_parent() {
+        let (tracer, calls) = tracer_with_sampler(SamplingDecision::RecordAndSample);
+
+        let mut parent_builder = SpanBuilder::from_name("parent".to_string());
+        let parent_reference = tracer.sampled_span_reference(&mut parent_builder);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let mut child_builder = SpanBuilder::from_name("child".to_string());
+        child_builder.parent_reference = Some(parent_reference);
+        let child_reference = tracer.sampled_span_reference(&mut child_builder);
+
+        // The sampler must run again for the child, even though it has a valid
+        // parent: a `ParentBased` or ratio sampler needs every span to go through
+        // `should_sample`, not just roots.
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_ne!(child_reference.trace_flags() & otel::TRACE_FLAG_SAMPLED, 0);
+    }
+
+    #[test]
+    fn dropped_decision_clears_the_sampled_flag() {
+        let (tracer, _calls) = tracer_with_sampler(SamplingDecision::Drop);
+
+        let mut builder = SpanBuilder::from_name("span".to_string());
+        let span_reference = tracer.sampled_span_reference(&mut builder);
+
+        assert_eq!(span_reference.trace_flags() & otel::TRACE_FLAG_SAMPLED, 0);
+    }
+
+    #[test]
+    fn reuses_a_cached_sampling_result_instead_of_resampling() {
+        let (tracer, calls) = tracer_with_sampler(SamplingDecision::RecordAndSample);
+
+        let mut builder = SpanBuilder::from_name("span".to_string());
+        tracer.sampled_span_reference(&mut builder);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // A second call against the same builder (e.g. from both `set_parent` and
+        // the eventual real span export) must reuse `builder.sampling_result`
+        // rather than invoking the sampler again.
+        tracer.sampled_span_reference(&mut builder);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn local_span_built_from_a_remote_parent_is_not_itself_remote() {
+        let (tracer, _calls) = tracer_with_sampler(SamplingDecision::RecordAndSample);
+
+        let remote_parent = SpanReference::new(
+            TraceId::from_u128(1),
+            SpanId::from_u64(1),
+            otel::TRACE_FLAG_SAMPLED,
+            true,
+            Default::default(),
+        );
+        let mut builder = SpanBuilder::from_name("span".to_string());
+        builder.parent_reference = Some(remote_parent);
+
+        let span_reference = tracer.sampled_span_reference(&mut builder);
+
+        // `is_remote` marks a `SpanReference` extracted from a remote context; a
+        // span built locally by this tracer must never report itself as remote,
+        // even when its parent was.
+        assert!(!span_reference.is_remote());
+    }
+
+    #[test]
+    fn parent_trace_state_survives_a_sampler_that_leaves_it_default() {
+        let (tracer, _calls) = tracer_with_sampler(SamplingDecision::RecordAndSample);
+
+        let parent_trace_state = otel::TraceState::from_key_value(vec![("vendor", "value")]).unwrap();
+        let parent = SpanReference::new(
+            TraceId::from_u128(1),
+            SpanId::from_u64(1),
+            otel::TRACE_FLAG_SAMPLED,
+            false,
+            parent_trace_state.clone(),
+        );
+        let mut builder = SpanBuilder::from_name("span".to_string());
+        builder.parent_reference = Some(parent);
+
+        let span_reference = tracer.sampled_span_reference(&mut builder);
+
+        // The sampler in this test leaves `trace_state` at its default, empty
+        // value, so the parent's vendor tracestate must still come through.
+        assert_eq!(span_reference.trace_state(), &parent_trace_state);
+    }
+
+    #[test]
+    fn sampler_trace_state_overrides_the_parents() {
+        let sampler_trace_state =
+            otel::TraceState::from_key_value(vec![("vendor", "sampled")]).unwrap();
+        let (tracer, _calls) = tracer_with_sampler_and_trace_state(
+            SamplingDecision::RecordAndSample,
+            sampler_trace_state.clone(),
+        );
+
+        let parent_trace_state = otel::TraceState::from_key_value(vec![("vendor", "value")]).unwrap();
+        let parent = SpanReference::new(
+            TraceId::from_u128(1),
+            SpanId::from_u64(1),
+            otel::TRACE_FLAG_SAMPLED,
+            false,
+            parent_trace_state,
+        );
+        let mut builder = SpanBuilder::from_name("span".to_string());
+        builder.parent_reference = Some(parent);
+
+        let span_reference = tracer.sampled_span_reference(&mut builder);
+
+        // A sampler that deliberately mutates `tracestate` to record its
+        // decision takes precedence over the parent's.
+        assert_eq!(span_reference.trace_state(), &sampler_trace_state);
+    }
+
+    #[derive(Debug)]
+    struct TestSpan(otel::SpanReference);
+
+    impl otel::Span for TestSpan {
+        fn span_reference(&self) -> otel::SpanReference {
+            self.0.clone()
+        }
+
+        fn is_recording(&self) -> bool {
+            false
+        }
+
+        fn set_attribute(&self, _attribute: otel::KeyValue) {}
+
+        fn set_status(&self, _code: otel::StatusCode, _message: String) {}
+
+        fn update_name(&self, _new_name: String) {}
+
+        fn end(&self) {}
+    }
+
+    #[test]
+    fn noop_tracer_falls_back_to_the_active_span_when_no_parent_is_set() {
+        let active_reference = SpanReference::new(
+            TraceId::from_u128(7),
+            SpanId::from_u64(7),
+            otel::TRACE_FLAG_SAMPLED,
+            true,
+            Default::default(),
+        );
+        let _guard =
+            otel::Context::current_with_span(TestSpan(active_reference.clone())).attach();
+
+        let tracer = otel::NoopTracer::default();
+        let mut builder = SpanBuilder::from_name("span".to_string());
+
+        let span_reference = tracer.sampled_span_reference(&mut builder);
+
+        assert_eq!(span_reference, active_reference);
+    }
+
+    #[test]
+    fn tracer_falls_back_to_the_active_span_once_its_provider_is_dropped() {
+        let active_reference = SpanReference::new(
+            TraceId::from_u128(9),
+            SpanId::from_u64(9),
+            otel::TRACE_FLAG_SAMPLED,
+            true,
+            Default::default(),
+        );
+        let _guard =
+            otel::Context::current_with_span(TestSpan(active_reference.clone())).attach();
+
+        let provider = sdk::trace::TracerProvider::default();
+        let tracer = provider.get_tracer("test", None);
+        drop(provider);
+
+        let mut builder = SpanBuilder::from_name("span".to_string());
+        let span_reference = tracer.sampled_span_reference(&mut builder);
+
+        assert_eq!(span_reference.trace_id(), active_reference.trace_id());
+        assert_eq!(span_reference.span_id(), active_reference.span_id());
+    }
 }